@@ -0,0 +1,93 @@
+use clap::{App, Arg, ArgMatches};
+
+use crate::{error::Error, notes, patchname::PatchName, signature, stack::Stack};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("comment", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("comment")
+        .about("Attach a review comment to a patch")
+        .long_about(
+            "Append a comment to a patch's review thread, stored as a git \
+             note under 'refs/notes/stgit/<branch>' keyed by the patch's \
+             commit id. Threads live outside the patch commits and the \
+             stack metadata, so they can be pushed and fetched \
+             independently for asynchronous review.",
+        )
+        .arg(
+            Arg::new("patchname")
+                .help("Patch to comment on")
+                .required(true),
+        )
+        .arg(
+            Arg::new("message")
+                .long("message")
+                .short('m')
+                .help("Comment text")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("reply")
+                .long("reply")
+                .help("Id of the comment this one replies to")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::new("show")
+                .long("show")
+                .help("Print the patch's comment thread instead of appending"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+    let stack = Stack::from_branch(&repo, branch_name)?;
+
+    let patchname = matches
+        .value_of("patchname")
+        .expect("required")
+        .parse::<PatchName>()?;
+    if !stack.has_patch(&patchname) {
+        return Err(Error::PatchNotFound(patchname));
+    }
+    let patch_oid = stack.patches[patchname.as_ref()].oid;
+
+    let branch_shorthand = crate::stack::branch_shorthand(&repo, branch_name)?;
+
+    if matches.is_present("show") {
+        for comment in notes::show_thread(&repo, &branch_shorthand, patch_oid)? {
+            let reply = comment
+                .parent
+                .as_deref()
+                .map(|id| format!(" (reply to #{})", id))
+                .unwrap_or_default();
+            println!(
+                "#{} {}{}\n{}\n",
+                comment.id, comment.author, reply, comment.body
+            );
+        }
+        return Ok(());
+    }
+
+    let config = repo.config()?;
+    let author = signature::make_author(Some(&config), matches)
+        .or_else(|_| repo.signature())
+        .map_err(Error::from)?;
+    let body = matches
+        .value_of("message")
+        .ok_or(Error::CommentMessageRequired)?;
+
+    let comment = notes::add_comment(
+        &repo,
+        &branch_shorthand,
+        patch_oid,
+        &author,
+        body,
+        matches.value_of("reply"),
+    )?;
+    println!("Added comment #{}", comment.id);
+    Ok(())
+}
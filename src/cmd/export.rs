@@ -0,0 +1,68 @@
+use clap::{App, Arg, ArgMatches, ValueHint};
+
+use crate::{bundle, stack::Stack};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("export", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("export")
+        .about("Export the stack as a self-contained git bundle")
+        .long_about(
+            "Package the applied patches (and optionally the unapplied \
+             ones) into a single self-contained git bundle file. The \
+             bundle can be fetched and unbundled by a reviewer without \
+             network access to the origin. Requires '--bundle', which \
+             exists to make the output format explicit at the call site; \
+             other export formats are not yet supported.",
+        )
+        .arg(
+            Arg::new("bundle")
+                .long("bundle")
+                .required(true)
+                .help("Write a self-contained git bundle (the only supported format)"),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Also include unapplied patches in the bundle"),
+        )
+        .arg(
+            Arg::new("output")
+                .long("output")
+                .short('o')
+                .help("Path of the file to write")
+                .value_hint(ValueHint::FilePath)
+                .takes_value(true),
+        )
+}
+
+fn run(matches: &ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+    let stack = Stack::from_branch(&repo, branch_name)?;
+
+    let include_unapplied = matches.is_present("all");
+    let stack_bundle = bundle::build_stack_bundle(&repo, &stack, include_unapplied)?;
+
+    let output_path = matches
+        .value_of_os("output")
+        .map(std::path::PathBuf::from)
+        .unwrap_or_else(|| std::path::PathBuf::from("stack.bundle"));
+    std::fs::write(&output_path, &stack_bundle.data)?;
+    std::fs::write(
+        output_path.with_extension("manifest"),
+        &stack_bundle.manifest,
+    )?;
+
+    println!(
+        "Wrote {} ({} bytes, sha256:{})",
+        output_path.display(),
+        stack_bundle.data.len(),
+        stack_bundle.hash
+    );
+
+    Ok(())
+}
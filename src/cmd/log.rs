@@ -0,0 +1,34 @@
+use clap::App;
+
+use crate::stack::Stack;
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("log", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("log")
+        .about("Show the stack's operation log")
+        .long_about(
+            "Print the history of operations performed on the current stack, \
+         newest first, as recorded under 'refs/stacks/<branch>'. Use 'stg \
+         undo'/'stg redo' to navigate it.",
+        )
+}
+
+fn run(_matches: &clap::ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+
+    for entry in Stack::log_iter(&repo, branch_name)? {
+        let entry = entry?;
+        let message = entry.message.lines().next().unwrap_or("");
+        println!(
+            "{}  {}  {}",
+            &entry.commit_oid.to_string()[..12],
+            entry.committer_time.seconds(),
+            message,
+        );
+    }
+    Ok(())
+}
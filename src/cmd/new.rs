@@ -5,9 +5,11 @@ use crate::{
     argset,
     commit::CommitData,
     error::Error,
+    invocation,
     patchdescription::PatchDescription,
     patchname::PatchName,
     signature,
+    signing::SignMode,
     stack::{ConflictMode, Stack, StackStateAccess},
 };
 
@@ -42,6 +44,17 @@ fn get_app() -> App<'static> {
                 .help("Show diff in message template"),
         )
         .arg(&*argset::HOOK_ARG)
+        .arg(
+            Arg::new("sign")
+                .long("sign")
+                .help("Sign the patch commit with GPG or SSH")
+                .conflicts_with("no-sign"),
+        )
+        .arg(
+            Arg::new("no-sign")
+                .long("no-sign")
+                .help("Do not sign the patch commit, overriding commit.gpgsign"),
+        )
         .arg(
             Arg::new("patchname")
                 .help("Name for new patch")
@@ -135,7 +148,9 @@ fn run(matches: &ArgMatches) -> super::Result {
 
     let message = patch_desc.message;
 
-    let mut cd = CommitData::new(patch_desc.author, committer, message, tree.id(), parents);
+    let sign_mode = SignMode::from_flags(matches.is_present("sign"), matches.is_present("no-sign"));
+    let mut cd = CommitData::new(patch_desc.author, committer, message, tree.id(), parents)
+        .with_sign_mode(sign_mode);
 
     if let Some(template_path) = matches.value_of_os("save-template") {
         std::fs::write(template_path, &cd.message)?;
@@ -183,6 +198,10 @@ fn run(matches: &ArgMatches) -> super::Result {
                 Ok(())
             },
         )
-        .execute(&format!("new: {}", patchname))?;
+        .execute(&format!(
+            "new: {}\n\n{}",
+            patchname,
+            invocation::command_line()
+        ))?;
     Ok(())
 }
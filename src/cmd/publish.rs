@@ -0,0 +1,53 @@
+use clap::{App, Arg, ArgMatches, ValueHint};
+
+use crate::{bundle, publish, stack::Stack};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("publish", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("publish")
+        .about("Upload the stack as a bundle to a remote drop endpoint")
+        .long_about(
+            "Build a self-contained git bundle from the stack (as 'stg \
+             export --bundle' does) and upload it to 'url' as a \
+             multipart/form-data POST, so a lightweight patch-inbox \
+             service can store and re-serve it. On success, the id the \
+             server assigns to the upload is printed.",
+        )
+        .arg(
+            Arg::new("url")
+                .help("URL of the drop endpoint to publish to")
+                .value_hint(ValueHint::Url)
+                .required(true),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Also include unapplied patches in the bundle"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+    let stack = Stack::from_branch(&repo, branch_name)?;
+    let branch_shorthand = crate::stack::branch_shorthand(&repo, branch_name)?;
+
+    let include_unapplied = matches.is_present("all");
+    let stack_bundle = bundle::build_stack_bundle(&repo, &stack, include_unapplied)?;
+
+    let mut patch_names: Vec<&String> = stack.applied.iter().collect();
+    if include_unapplied {
+        patch_names.extend(stack.unapplied.iter());
+    }
+
+    let url = matches.value_of("url").expect("required");
+    let bundle_id =
+        publish::publish_bundle(&repo, url, &branch_shorthand, &patch_names, &stack_bundle)?;
+
+    println!("Published sha256:{} as {}", stack_bundle.hash, bundle_id);
+    Ok(())
+}
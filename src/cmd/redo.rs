@@ -0,0 +1,85 @@
+use clap::App;
+use git2::Oid;
+
+use crate::{
+    error::Error,
+    stack::{self, UNDO_REDO_MESSAGE_PREFIX},
+};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("redo", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("redo")
+        .about("Redo the last undone stack operation")
+        .long_about(
+            "Reapply an operation previously reverted with 'stg undo', using \
+         the reflog of 'refs/stacks/<branch>' to find the state that was \
+         current right before the undo.",
+        )
+}
+
+fn run(_matches: &clap::ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+
+    let refname = stack::stack_refname(&repo, branch_name)?;
+    let current = repo
+        .find_reference(&refname)?
+        .target()
+        .ok_or(Error::StGitStackMetadataNotFound)?;
+
+    let redo_target = find_redo_target(&repo, &refname, current)?.ok_or(Error::NothingToRedo)?;
+
+    // The redo target is newer than the current tip, so it is not in the
+    // `prev` chain that `Stack::log_iter` walks; load its state directly.
+    let commit = repo
+        .find_commit(redo_target)
+        .map_err(|_| Error::RedoTargetNotInHistory)?;
+    let stack_json = commit
+        .tree()?
+        .get_name("stack.json")
+        .ok_or(Error::StGitStackMetadataNotFound)?
+        .to_object(&repo)?
+        .peel_to_blob()?;
+    let entry = stack::LogEntry {
+        stack: serde_json::from_slice(stack_json.content())?,
+        commit_oid: redo_target,
+        committer_time: commit.committer().when(),
+        message: commit.message().unwrap_or("").to_string(),
+    };
+    stack::checkout_log_entry(&repo, branch_name, &entry)?;
+    println!("Redid 1 operation");
+    Ok(())
+}
+
+/// Scan the reflog of `refname` for the transition that moved *away from*
+/// `current`, i.e. the state that `stg undo` most recently replaced.
+///
+/// After more than one undo/redo in a session, the same oid can appear as
+/// `id_old()` in two different entries: the genuine forward operation that
+/// produced it, and a later `undo` that moved away from it again. Only the
+/// former is a valid redo target, so entries written by `checkout_log_entry`
+/// itself (identifiable by its `"undo/redo to: "` message prefix) are
+/// skipped here rather than matched.
+fn find_redo_target(
+    repo: &git2::Repository,
+    refname: &str,
+    current: Oid,
+) -> Result<Option<Oid>, Error> {
+    let reflog = repo.reflog(refname)?;
+    for entry in reflog.iter() {
+        if entry.id_old() == current {
+            if entry
+                .message()
+                .unwrap_or("")
+                .starts_with(UNDO_REDO_MESSAGE_PREFIX)
+            {
+                continue;
+            }
+            return Ok(Some(entry.id_new()));
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,82 @@
+use clap::{App, Arg, ArgMatches};
+
+use crate::{
+    commit::CommitData,
+    error::Error,
+    invocation, notes,
+    signing::SignMode,
+    stack::{PatchDescriptor, Stack},
+};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("refresh", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("refresh")
+        .about("Update the top patch with the current index")
+        .long_about(
+            "Amend the topmost applied patch with the tree currently \
+             staged in the index, keeping its author and message. The \
+             patch's commit id changes as a result, so any review thread \
+             attached to it with 'stg comment' is re-anchored to the new \
+             id rather than left on the now-unreachable old one.",
+        )
+        .arg(
+            Arg::new("sign")
+                .long("sign")
+                .help("Sign the refreshed commit with GPG or SSH")
+                .conflicts_with("no-sign"),
+        )
+        .arg(
+            Arg::new("no-sign")
+                .long("no-sign")
+                .help("Do not sign the refreshed commit, overriding commit.gpgsign"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+    let mut stack = Stack::from_branch(&repo, branch_name)?;
+    let branch_shorthand = crate::stack::branch_shorthand(&repo, branch_name)?;
+
+    let patchname = stack
+        .applied
+        .last()
+        .cloned()
+        .ok_or(Error::NoAppliedPatches)?;
+    let old_oid = stack.patches[&patchname].oid;
+    let old_commit = repo.find_commit(old_oid)?;
+
+    let tree_id = repo.index()?.write_tree()?;
+    let sign_mode = SignMode::from_flags(matches.is_present("sign"), matches.is_present("no-sign"));
+    let cd = CommitData::new(
+        old_commit.author().to_owned(),
+        repo.signature()?.to_owned(),
+        old_commit.message().unwrap_or("").to_string(),
+        tree_id,
+        vec![old_commit.parent_id(0)?],
+    )
+    .with_sign_mode(sign_mode);
+    let new_oid = cd.commit(&repo)?;
+
+    stack
+        .patches
+        .insert(patchname.clone(), PatchDescriptor { oid: new_oid });
+
+    let stack_refname = crate::stack::stack_refname(&repo, branch_name)?;
+    stack.commit(
+        &repo,
+        Some(&stack_refname),
+        &format!("refresh: {}\n\n{}", patchname, invocation::command_line()),
+    )?;
+
+    notes::reanchor(&repo, &branch_shorthand, old_oid, new_oid)?;
+
+    let top_commit = repo.find_commit(stack.top())?;
+    repo.reset(top_commit.as_object(), git2::ResetType::Mixed, None)?;
+
+    println!("Refreshed patch \"{}\"", patchname);
+    Ok(())
+}
@@ -0,0 +1,68 @@
+use clap::{App, Arg, ArgMatches};
+use git2::Oid;
+
+use crate::{error::Error, snapshot, stack::Stack};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("snapshot", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("snapshot")
+        .about("Pin the current stack state for later recovery")
+        .long_about(
+            "Write a tagged, timestamped copy of the current stack state \
+             under 'refs/snapshots/<branch>/<timestamp>'. With \
+             '--incremental', only the diff against the most recent \
+             snapshot is stored; otherwise the complete state is written. \
+             Use this to cheaply pin a point in history to return to, or \
+             to recover after a crash mid-operation.",
+        )
+        .arg(
+            Arg::new("incremental")
+                .long("incremental")
+                .help("Store only the diff against the most recent snapshot")
+                .conflicts_with("restore"),
+        )
+        .arg(
+            Arg::new("restore")
+                .long("restore")
+                .help("Restore the stack to a pinned snapshot instead of writing a new one")
+                .takes_value(true)
+                .min_values(0)
+                .value_name("SNAPSHOT"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+    let branch_shorthand = crate::stack::branch_shorthand(&repo, branch_name)?;
+
+    if matches.is_present("restore") {
+        let snapshot_blob = match matches.value_of("restore") {
+            Some(oid_str) => Some(
+                Oid::from_str(oid_str)
+                    .map_err(|source| Error::InvalidOid(oid_str.to_string(), source))?,
+            ),
+            None => None,
+        };
+        let restored = snapshot::restore(&repo, &branch_shorthand, snapshot_blob)?;
+        crate::stack::checkout_stack(&repo, branch_name, restored, "snapshot restore")?;
+        println!("Restored stack from snapshot");
+        return Ok(());
+    }
+
+    let stack = Stack::from_branch(&repo, branch_name)?;
+    let timestamp = repo.signature()?.when().seconds();
+    let incremental = matches.is_present("incremental");
+
+    let blob_oid = snapshot::write(&repo, &stack, &branch_shorthand, incremental, timestamp)?;
+    println!(
+        "Wrote {} snapshot {} at {}",
+        if incremental { "incremental" } else { "full" },
+        blob_oid,
+        timestamp,
+    );
+    Ok(())
+}
@@ -0,0 +1,53 @@
+use clap::{App, Arg, ArgMatches};
+
+use crate::{
+    error::Error,
+    stack::{checkout_log_entry, Stack},
+};
+
+pub(super) fn get_command() -> (&'static str, super::StGitCommand) {
+    ("undo", super::StGitCommand { get_app, run })
+}
+
+fn get_app() -> App<'static> {
+    App::new("undo")
+        .about("Undo the last N stack operations")
+        .long_about(
+            "Revert the stack to the state it was in before the last N \
+             operations, using the operation log recorded under \
+             'refs/stacks/<branch>'. The current head is checked out to \
+             match the restored state.",
+        )
+        .arg(
+            Arg::new("number")
+                .long("number")
+                .short('n')
+                .help("Number of operations to undo")
+                .takes_value(true)
+                .default_value("1"),
+        )
+}
+
+fn run(matches: &ArgMatches) -> super::Result {
+    let repo = git2::Repository::open_from_env()?;
+    let branch_name: Option<&str> = None;
+
+    let n: usize = matches
+        .value_of("number")
+        .expect("has a default value")
+        .parse()
+        .map_err(|_| Error::UndoCountInvalid(matches.value_of("number").unwrap().to_string()))?;
+
+    let mut log_iter = Stack::log_iter(&repo, branch_name)?;
+    log_iter.next(); // the current state itself; skip it
+
+    let mut entry = None;
+    for _ in 0..n {
+        entry = Some(log_iter.next().ok_or(Error::NothingToUndo)??);
+    }
+    let entry = entry.ok_or(Error::NothingToUndo)?;
+
+    checkout_log_entry(&repo, branch_name, &entry)?;
+    println!("Undid {} operation(s)", n);
+    Ok(())
+}
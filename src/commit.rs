@@ -0,0 +1,62 @@
+use git2::{Oid, Repository, Signature};
+
+pub(crate) use crate::error::Error;
+use crate::signing::{commit_possibly_signed, SignMode};
+
+/// The pieces of a not-yet-created commit, threaded through hooks (commit-msg)
+/// and templates before being turned into an actual git object.
+pub(crate) struct CommitData {
+    pub author: Signature<'static>,
+    pub committer: Signature<'static>,
+    pub message: String,
+    pub tree_id: Oid,
+    pub parents: Vec<Oid>,
+    sign_mode: SignMode,
+}
+
+impl CommitData {
+    pub fn new(
+        author: Signature<'static>,
+        committer: Signature<'static>,
+        message: String,
+        tree_id: Oid,
+        parents: Vec<Oid>,
+    ) -> Self {
+        Self {
+            author,
+            committer,
+            message,
+            tree_id,
+            parents,
+            sign_mode: SignMode::Default,
+        }
+    }
+
+    /// Override whether this commit gets GPG/SSH-signed, independent of the
+    /// repo's `commit.gpgsign` config. Used by `stg new --sign`/`--no-sign`.
+    pub fn with_sign_mode(mut self, sign_mode: SignMode) -> Self {
+        self.sign_mode = sign_mode;
+        self
+    }
+
+    pub fn commit(&self, repo: &Repository) -> Result<Oid, Error> {
+        let tree = repo.find_tree(self.tree_id)?;
+        let parents: Vec<git2::Commit> = self
+            .parents
+            .iter()
+            .map(|oid| repo.find_commit(*oid))
+            .collect::<Result<_, _>>()?;
+        let parents: Vec<&git2::Commit> = parents.iter().collect();
+
+        Ok(commit_possibly_signed(
+            repo,
+            self.sign_mode,
+            None,
+            &self.author,
+            &self.committer,
+            &self.message,
+            &tree,
+            &parents,
+        )?)
+    }
+}
@@ -0,0 +1,96 @@
+use crate::patchname::PatchName;
+
+/// The single error type threaded through every `stg` command, built with
+/// `thiserror` like the rest of the external crates this one leans on.
+#[derive(thiserror::Error, Debug)]
+pub(crate) enum Error {
+    #[error(transparent)]
+    Git(#[from] git2::Error),
+
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Fmt(#[from] std::fmt::Error),
+
+    #[error(transparent)]
+    Utf8(#[from] std::string::FromUtf8Error),
+
+    #[error("invalid stack metadata: {source}")]
+    JsonError {
+        #[from]
+        source: serde_json::Error,
+    },
+
+    #[error("HEAD is detached")]
+    HeadDetached,
+
+    #[error("branch name is not valid UTF-8")]
+    StGitNonUtf8Name,
+
+    #[error("stack metadata not found")]
+    StGitStackMetadataNotFound,
+
+    #[error("stack already initialized for branch `{0}`")]
+    StGitStackAlreadyInitialized(String),
+
+    #[error("branch `{0}` does not have an initialized stack")]
+    StGitStackNotInitialized(String),
+
+    #[error("patch `{0}` already exists")]
+    PatchAlreadyExists(PatchName),
+
+    #[error("no `user.signingkey` configured for signing")]
+    SigningKeyNotConfigured,
+
+    #[error("ref `{0}` has no direct target to update against")]
+    RefHasNoTarget(String),
+
+    #[error("ref `{0}` was concurrently updated; refusing to overwrite it")]
+    RefUpdateConflict(String),
+
+    #[error("failed to spawn signer `{program}`: {source}")]
+    SignerSpawnFailed {
+        program: String,
+        source: std::io::Error,
+    },
+
+    #[error("signer `{program}` failed: {stderr}")]
+    SignerFailed { program: String, stderr: String },
+
+    #[error("--number must be a non-negative integer, got `{0}`")]
+    UndoCountInvalid(String),
+
+    #[error("nothing to undo")]
+    NothingToUndo,
+
+    #[error("nothing to redo")]
+    NothingToRedo,
+
+    #[error("redo target is no longer part of the stack history")]
+    RedoTargetNotInHistory,
+
+    #[error("patch `{0}` not found")]
+    PatchNotFound(PatchName),
+
+    #[error("--message is required unless --show is given")]
+    CommentMessageRequired,
+
+    #[error("no patch applied")]
+    NoAppliedPatches,
+
+    #[error("`{0}` is not a valid object id: {1}")]
+    InvalidOid(String, git2::Error),
+
+    #[error("invalid `http.proxy` value: {0}")]
+    InvalidProxyConfig(String),
+
+    #[error("publish to {url} failed: {message}")]
+    PublishRequestFailed { url: String, message: String },
+
+    #[error("server response was not valid UTF-8")]
+    PublishResponseNotUtf8,
+
+    #[error("server response did not contain a bundle id: {0}")]
+    PublishResponseMissingBundleId(String),
+}
@@ -0,0 +1,9 @@
+/// Recreate the command line that invoked the current `stg` process.
+///
+/// Operation-log entries are most useful when they're self-describing, so
+/// commands that write a stack-state commit append this to their message,
+/// the same way `jj`'s operation log records the command that produced each
+/// entry.
+pub(crate) fn command_line() -> String {
+    std::env::args().collect::<Vec<_>>().join(" ")
+}
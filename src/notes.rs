@@ -0,0 +1,104 @@
+use git2::{Oid, Repository, Signature};
+use serde::{Deserialize, Serialize};
+
+pub(crate) use crate::error::Error;
+
+/// One comment in a patch's review thread.
+///
+/// `id` is this comment's position in the thread (stringified), which is
+/// enough to address it for `--reply`. `parent` names the comment this one
+/// replies to, or is `None` for a top-level comment.
+#[derive(Clone, Serialize, Deserialize)]
+pub(crate) struct Comment {
+    pub id: String,
+    pub author: String,
+    pub timestamp: i64,
+    pub parent: Option<String>,
+    pub body: String,
+}
+
+/// An append-only list of [`Comment`]s attached to a single patch commit.
+#[derive(Default, Serialize, Deserialize)]
+pub(crate) struct Thread(Vec<Comment>);
+
+impl Thread {
+    pub fn comments(&self) -> &[Comment] {
+        &self.0
+    }
+}
+
+/// The notes ref a branch's review threads are stored under, mirroring how
+/// `refs/stacks/<branch>` holds the branch's stack state. Because notes
+/// live in their own ref namespace, they can be pushed and fetched
+/// independently of the stack or the patch commits themselves.
+fn notes_refname(branch_shorthand: &str) -> String {
+    format!("refs/notes/stgit/{}", branch_shorthand)
+}
+
+fn load_thread(repo: &Repository, notes_ref: &str, patch_oid: Oid) -> Result<Thread, Error> {
+    match repo.find_note(Some(notes_ref), patch_oid) {
+        Ok(note) => {
+            let content = note.message().ok_or(Error::StGitNonUtf8Name)?;
+            Ok(serde_json::from_str(content)?)
+        }
+        Err(_) => Ok(Thread::default()),
+    }
+}
+
+/// Append a comment to `patch_oid`'s review thread, optionally as a reply to
+/// an existing comment, and return the record that was added.
+pub(crate) fn add_comment(
+    repo: &Repository,
+    branch_shorthand: &str,
+    patch_oid: Oid,
+    author: &Signature,
+    body: &str,
+    reply_to: Option<&str>,
+) -> Result<Comment, Error> {
+    let notes_ref = notes_refname(branch_shorthand);
+    let mut thread = load_thread(repo, &notes_ref, patch_oid)?;
+
+    let comment = Comment {
+        id: thread.0.len().to_string(),
+        author: author.to_string(),
+        timestamp: author.when().seconds(),
+        parent: reply_to.map(str::to_string),
+        body: body.to_string(),
+    };
+    thread.0.push(comment.clone());
+
+    let content = serde_json::to_string_pretty(&thread)?;
+    repo.note(author, author, Some(&notes_ref), patch_oid, &content, true)?;
+
+    Ok(comment)
+}
+
+/// Render `patch_oid`'s review thread for display by `stg comment --show`.
+pub(crate) fn show_thread(
+    repo: &Repository,
+    branch_shorthand: &str,
+    patch_oid: Oid,
+) -> Result<Vec<Comment>, Error> {
+    let notes_ref = notes_refname(branch_shorthand);
+    Ok(load_thread(repo, &notes_ref, patch_oid)?.0)
+}
+
+/// Move `old_oid`'s review thread (if it has one) to `new_oid`, called by
+/// `stg refresh` after a patch's commit id changes so its thread stays
+/// attached to the patch rather than being orphaned on the now-unreachable
+/// old commit.
+pub(crate) fn reanchor(
+    repo: &Repository,
+    branch_shorthand: &str,
+    old_oid: Oid,
+    new_oid: Oid,
+) -> Result<(), Error> {
+    let notes_ref = notes_refname(branch_shorthand);
+    if let Ok(note) = repo.find_note(Some(&notes_ref), old_oid) {
+        let content = note.message().ok_or(Error::StGitNonUtf8Name)?.to_string();
+        let sig = repo.signature()?;
+        repo.note(&sig, &sig, Some(&notes_ref), new_oid, &content, true)?;
+        repo.note_delete(old_oid, Some(&notes_ref), &sig, &sig)?;
+    }
+    Ok(())
+}
@@ -0,0 +1,152 @@
+use git2::Repository;
+
+use crate::bundle::StackBundle;
+pub(crate) use crate::error::Error;
+
+const BOUNDARY: &str = "stgit-bundle-boundary-f3a9c1";
+
+/// Upload `bundle` to `url` as a `multipart/form-data` POST: a `bundle` part
+/// carrying the raw bundle bytes (addressed by its SHA-256 digest, the same
+/// way the external patch-bundle tooling's lazy multipart client does it),
+/// and a `metadata` part describing what the bundle contains. Honors the
+/// repo's unscoped `http.proxy` and every `http.extraHeader` entry that
+/// applies to `url` (see [`extra_headers`]); other `http.*` settings, such
+/// as URL-scoped proxies or credential helpers, are not read.
+/// Returns the bundle id the server reports back on success.
+pub(crate) fn publish_bundle(
+    repo: &Repository,
+    url: &str,
+    branch_shorthand: &str,
+    patch_names: &[&String],
+    bundle: &StackBundle,
+) -> Result<String, Error> {
+    let body = build_multipart_body(branch_shorthand, patch_names, bundle);
+
+    let config = repo.config()?;
+    let mut agent_builder = ureq::AgentBuilder::new();
+    if let Ok(proxy_url) = config.get_string("http.proxy") {
+        agent_builder = agent_builder
+            .proxy(ureq::Proxy::new(&proxy_url).map_err(|_| Error::InvalidProxyConfig(proxy_url))?);
+    }
+    let agent = agent_builder.build();
+
+    let mut request = agent.post(url).set(
+        "Content-Type",
+        &format!("multipart/form-data; boundary={}", BOUNDARY),
+    );
+    for (name, value) in extra_headers(&config, url)? {
+        request = request.set(&name, &value);
+    }
+
+    let response = request
+        .send_bytes(&body)
+        .map_err(|e| Error::PublishRequestFailed {
+            url: url.to_string(),
+            message: e.to_string(),
+        })?;
+    let response_body = response
+        .into_string()
+        .map_err(|_| Error::PublishResponseNotUtf8)?;
+
+    extract_bundle_id(&response_body)
+        .ok_or_else(|| Error::PublishResponseMissingBundleId(response_body))
+}
+
+/// Read every `http.extraHeader` entry that applies to `url` (it is
+/// multi-valued, and any number of unrelated headers may be configured
+/// there, e.g. for a credential helper that injects `Authorization` via
+/// `http.<url>.extraHeader` for one specific remote) and split each
+/// `"Name: value"` line into the header it actually names, rather than
+/// assuming it is always an `Authorization` value.
+///
+/// `config.entries(Some("http.extraheader"))` only matches the unscoped
+/// `http.extraHeader` key: its pattern requires "http" and "extraheader" to
+/// be contiguous, which a URL-scoped key like
+/// `http.https://example.com/.extraHeader` breaks up. So every entry is
+/// iterated instead, matched by checking its name starts with `http.` and
+/// ends with `.extraheader`, case-insensitively, the way git itself matches
+/// section.subsection.key for this kind of config. The subsection in
+/// between, if any, is then checked against `url` on a URL component
+/// boundary -- the same way git scopes `http.<url>.*` config -- so a header
+/// scoped to one remote isn't also sent to an unrelated `stg publish`
+/// destination that merely shares a string prefix (e.g. a sibling
+/// subdomain).
+fn extra_headers(config: &git2::Config, url: &str) -> Result<Vec<(String, String)>, Error> {
+    const PREFIX: &str = "http.";
+    const SUFFIX: &str = ".extraheader";
+
+    let mut headers = Vec::new();
+    let mut entries = config.entries(None)?;
+    while let Some(entry) = entries.next() {
+        let entry = entry?;
+        let name = entry.name().unwrap_or("");
+        let lower = name.to_lowercase();
+        if !lower.starts_with(PREFIX) || !lower.ends_with(SUFFIX) {
+            continue;
+        }
+
+        // The unscoped "http.extraheader" shares its dot between PREFIX and
+        // SUFFIX, so the range is empty-or-inverted there: no scope.
+        let url_scope = name
+            .get(PREFIX.len()..name.len().saturating_sub(SUFFIX.len()))
+            .unwrap_or("");
+        if !url_scope.is_empty() {
+            match url.strip_prefix(url_scope) {
+                // Require the match to end on a URL component boundary, not
+                // just share a string prefix, so a header scoped to
+                // "https://example.com" doesn't also apply to
+                // "https://example.com.evil.org".
+                Some(rest)
+                    if url_scope.ends_with('/') || rest.is_empty() || rest.starts_with('/') => {}
+                _ => continue,
+            }
+        }
+
+        if let Some(value) = entry.value() {
+            if let Some((name, value)) = value.split_once(':') {
+                headers.push((name.trim().to_string(), value.trim().to_string()));
+            }
+        }
+    }
+    Ok(headers)
+}
+
+fn build_multipart_body(
+    branch_shorthand: &str,
+    patch_names: &[&String],
+    bundle: &StackBundle,
+) -> Vec<u8> {
+    let metadata = serde_json::json!({
+        "branch": branch_shorthand,
+        "patches": patch_names,
+        "hash": format!("sha256:{}", bundle.hash),
+    })
+    .to_string();
+
+    let mut body = Vec::new();
+    body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+    body.extend_from_slice(b"Content-Disposition: form-data; name=\"metadata\"\r\n");
+    body.extend_from_slice(b"Content-Type: application/json\r\n\r\n");
+    body.extend_from_slice(metadata.as_bytes());
+    body.extend_from_slice(b"\r\n");
+
+    body.extend_from_slice(format!("--{}\r\n", BOUNDARY).as_bytes());
+    body.extend_from_slice(
+        format!(
+            "Content-Disposition: form-data; name=\"bundle\"; filename=\"sha256-{}.bundle\"\r\n",
+            bundle.hash
+        )
+        .as_bytes(),
+    );
+    body.extend_from_slice(b"Content-Type: application/x-git-bundle\r\n\r\n");
+    body.extend_from_slice(&bundle.data);
+    body.extend_from_slice(b"\r\n");
+
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    body
+}
+
+fn extract_bundle_id(response_body: &str) -> Option<String> {
+    let value: serde_json::Value = serde_json::from_str(response_body).ok()?;
+    value.get("id")?.as_str().map(str::to_string)
+}
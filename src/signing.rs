@@ -0,0 +1,217 @@
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use git2::{Config, Repository};
+
+pub(crate) use crate::error::Error;
+
+/// How a commit's signature (if any) should be determined.
+///
+/// `Default` defers to the repository's `commit.gpgsign` config, matching
+/// plain `git commit`'s behavior; `stg new --sign`/`--no-sign` map to the
+/// other two variants.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum SignMode {
+    Default,
+    Sign,
+    NoSign,
+}
+
+impl SignMode {
+    pub(crate) fn from_flags(sign: bool, no_sign: bool) -> Self {
+        if no_sign {
+            SignMode::NoSign
+        } else if sign {
+            SignMode::Sign
+        } else {
+            SignMode::Default
+        }
+    }
+
+    fn should_sign(self, config: &Config) -> bool {
+        match self {
+            SignMode::Sign => true,
+            SignMode::NoSign => false,
+            SignMode::Default => config.get_bool("commit.gpgsign").unwrap_or(false),
+        }
+    }
+}
+
+/// Create a commit, signing it with the configured GPG or SSH signer when
+/// `sign_mode` (together with the repo's `commit.gpgsign`/`gpg.format`/
+/// `user.signingkey` config) calls for it.
+///
+/// This mirrors what plain `git commit -S` does under the hood: the commit
+/// object is first serialized to its raw buffer with
+/// `commit_create_buffer()`, the buffer is piped to the signer program to
+/// obtain a detached, ASCII-armored signature, and the object is finalized
+/// with `commit_signed()` using a `gpgsig` header. The target ref, if any,
+/// is updated afterward with the same compare-and-swap semantics
+/// `Repository::commit()` uses internally: the ref's current target is
+/// pinned to `parents[0]` *before* shelling out to the signer (which can
+/// block for seconds on a passphrase prompt), and the update is rejected
+/// if the ref has moved away from that pinned value by the time signing
+/// completes, so a concurrent update during the signing prompt is
+/// detected instead of silently overwritten.
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn commit_possibly_signed<'repo>(
+    repo: &'repo Repository,
+    sign_mode: SignMode,
+    update_ref: Option<&str>,
+    author: &git2::Signature,
+    committer: &git2::Signature,
+    message: &str,
+    tree: &git2::Tree,
+    parents: &[&git2::Commit],
+) -> Result<git2::Oid, Error> {
+    let config = repo.config()?;
+
+    if !sign_mode.should_sign(&config) {
+        return Ok(repo.commit(update_ref, author, committer, message, tree, parents)?);
+    }
+
+    let expected_ref_state = if let Some(refname) = update_ref {
+        let parent = parents.first().map(|c| c.id());
+        match repo.find_reference(refname) {
+            Ok(reference) => {
+                let current = reference
+                    .target()
+                    .ok_or_else(|| Error::RefHasNoTarget(refname.to_string()))?;
+                if Some(current) != parent {
+                    return Err(Error::RefUpdateConflict(refname.to_string()));
+                }
+                Some(current)
+            }
+            Err(_) => None,
+        }
+    } else {
+        None
+    };
+
+    let buffer = repo.commit_create_buffer(author, committer, message, tree, parents)?;
+    let buffer = buffer.as_str().ok_or(Error::StGitNonUtf8Name)?;
+    let signature = sign_buffer(&config, buffer)?;
+
+    let commit_content = repo.commit_signed(buffer, &signature, Some("gpgsig"))?;
+
+    if let Some(refname) = update_ref {
+        match expected_ref_state {
+            Some(current) => {
+                repo.reference_matching(refname, commit_content, true, current, message)?;
+            }
+            None => {
+                repo.reference(refname, commit_content, true, message)?;
+            }
+        }
+    }
+
+    Ok(commit_content)
+}
+
+/// Invoke the configured GPG or SSH signer on `buffer` and return the
+/// ASCII-armored detached signature it produces.
+fn sign_buffer(config: &Config, buffer: &str) -> Result<String, Error> {
+    let format = config
+        .get_string("gpg.format")
+        .unwrap_or_else(|_| "openpgp".to_string());
+    let signing_key = config
+        .get_string("user.signingkey")
+        .map_err(|_| Error::SigningKeyNotConfigured)?;
+
+    match format.as_str() {
+        "ssh" => sign_buffer_ssh(config, &signing_key, buffer),
+        _ => sign_buffer_gpg(config, &signing_key, buffer),
+    }
+}
+
+/// `ssh-keygen -Y sign` takes the data to sign as a file path, not stdin,
+/// and writes the detached signature alongside it as `<path>.sig` rather
+/// than to stdout, so `buffer` is spooled to a temp file first.
+fn sign_buffer_ssh(config: &Config, signing_key: &str, buffer: &str) -> Result<String, Error> {
+    let program = config
+        .get_string("gpg.ssh.program")
+        .unwrap_or_else(|_| "ssh-keygen".to_string());
+
+    let data_path = std::env::temp_dir().join(format!("stgit-sign-{}.data", std::process::id()));
+    std::fs::write(&data_path, buffer.as_bytes())?;
+    let sig_path = data_path.with_extension("data.sig");
+
+    let output = Command::new(&program)
+        .arg("-Y")
+        .arg("sign")
+        .arg("-n")
+        .arg("git")
+        .arg("-f")
+        .arg(signing_key)
+        .arg(&data_path)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .output()
+        .map_err(|source| {
+            let _ = std::fs::remove_file(&data_path);
+            Error::SignerSpawnFailed {
+                program: program.clone(),
+                source,
+            }
+        })?;
+
+    let signature = if output.status.success() {
+        std::fs::read_to_string(&sig_path)
+    } else {
+        Err(std::io::Error::new(
+            std::io::ErrorKind::Other,
+            "signing failed",
+        ))
+    };
+
+    let _ = std::fs::remove_file(&data_path);
+    let _ = std::fs::remove_file(&sig_path);
+
+    if !output.status.success() {
+        return Err(Error::SignerFailed {
+            program,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    Ok(signature?)
+}
+
+/// `gpg --detach-sign` happily signs data piped to it on stdin and writes
+/// the ASCII-armored signature to stdout.
+fn sign_buffer_gpg(config: &Config, signing_key: &str, buffer: &str) -> Result<String, Error> {
+    let program = config
+        .get_string("gpg.program")
+        .unwrap_or_else(|_| "gpg".to_string());
+
+    let mut child = Command::new(&program)
+        .arg("-bsau")
+        .arg(signing_key)
+        .arg("--status-fd=2")
+        .arg("--armor")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|source| Error::SignerSpawnFailed {
+            program: program.clone(),
+            source,
+        })?;
+
+    child
+        .stdin
+        .take()
+        .expect("stdin was piped")
+        .write_all(buffer.as_bytes())?;
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err(Error::SignerFailed {
+            program,
+            stderr: String::from_utf8_lossy(&output.stderr).into_owned(),
+        });
+    }
+
+    Ok(String::from_utf8(output.stdout)?)
+}
@@ -0,0 +1,275 @@
+use std::collections::BTreeMap;
+
+use git2::{Oid, Repository};
+use serde::{Deserialize, Serialize};
+
+pub(crate) use crate::error::Error;
+use crate::stack::{PatchDescriptor, Stack};
+
+/// The patch names added to and removed from one of the stack's patch lists
+/// (applied, unapplied or hidden) between two snapshots.
+#[derive(Serialize, Deserialize)]
+struct ListDelta {
+    #[serde(default)]
+    added: Vec<String>,
+    #[serde(default)]
+    removed: Vec<String>,
+}
+
+impl ListDelta {
+    fn between(before: &[String], after: &[String]) -> Self {
+        let added = after
+            .iter()
+            .filter(|n| !before.contains(n))
+            .cloned()
+            .collect();
+        let removed = before
+            .iter()
+            .filter(|n| !after.contains(n))
+            .cloned()
+            .collect();
+        Self { added, removed }
+    }
+
+    fn apply(&self, list: &mut Vec<String>) {
+        list.retain(|name| !self.removed.contains(name));
+        for name in &self.added {
+            if !list.contains(name) {
+                list.push(name.clone());
+            }
+        }
+    }
+}
+
+/// An incremental snapshot: only what changed since `parent`, rather than
+/// the complete stack state.
+#[derive(Serialize, Deserialize)]
+struct Incremental {
+    parent: Oid,
+    new_head: Oid,
+    changed_patches: BTreeMap<String, String>,
+    removed_patches: Vec<String>,
+    applied_delta: ListDelta,
+    unapplied_delta: ListDelta,
+    hidden_delta: ListDelta,
+}
+
+/// Either a complete stack state, or a diff against a parent snapshot.
+#[derive(Serialize, Deserialize)]
+enum SnapshotContent {
+    Full(Stack),
+    Incremental(Incremental),
+}
+
+fn snapshots_glob(branch_shorthand: &str) -> String {
+    format!("refs/snapshots/{}/*", branch_shorthand)
+}
+
+fn snapshot_refname(branch_shorthand: &str, timestamp: i64) -> String {
+    format!("refs/snapshots/{}/{:020}", branch_shorthand, timestamp)
+}
+
+fn most_recent_snapshot<'repo>(
+    repo: &'repo Repository,
+    branch_shorthand: &str,
+) -> Result<Option<git2::Reference<'repo>>, Error> {
+    let mut refnames: Vec<String> = repo
+        .references_glob(&snapshots_glob(branch_shorthand))?
+        .names()
+        .filter_map(|n| n.ok().map(str::to_string))
+        .collect();
+    refnames.sort();
+    match refnames.pop() {
+        Some(refname) => Ok(Some(repo.find_reference(&refname)?)),
+        None => Ok(None),
+    }
+}
+
+/// Write a snapshot of `stack` under `refs/snapshots/<branch>/<timestamp>`.
+///
+/// With `incremental`, only the diff against the most recent snapshot is
+/// stored (changed patch OIDs, removed patch names, and add/remove deltas
+/// for the applied/unapplied/hidden lists); without a prior snapshot to
+/// diff against, a full snapshot is written regardless. A full snapshot
+/// always stores the complete `stack.json`-equivalent state.
+pub(crate) fn write(
+    repo: &Repository,
+    stack: &Stack,
+    branch_shorthand: &str,
+    incremental: bool,
+    timestamp: i64,
+) -> Result<Oid, Error> {
+    let parent = most_recent_snapshot(repo, branch_shorthand)?;
+
+    let content = match parent.as_ref() {
+        Some(parent_ref) if incremental => {
+            let parent_oid = parent_ref
+                .target()
+                .ok_or(Error::StGitStackMetadataNotFound)?;
+            let parent_stack = restore(repo, branch_shorthand, Some(parent_oid))?;
+
+            let mut changed_patches = BTreeMap::new();
+            let mut removed_patches = Vec::new();
+            for name in stack.all_patches() {
+                let oid = stack.patches[name].oid;
+                if parent_stack.patches.get(name).map(|p| p.oid) != Some(oid) {
+                    changed_patches.insert(name.clone(), oid.to_string());
+                }
+            }
+            for name in parent_stack.all_patches() {
+                if !stack.patches.contains_key(name) {
+                    removed_patches.push(name.clone());
+                }
+            }
+
+            SnapshotContent::Incremental(Incremental {
+                parent: parent_oid,
+                new_head: stack.head,
+                changed_patches,
+                removed_patches,
+                applied_delta: ListDelta::between(&parent_stack.applied, &stack.applied),
+                unapplied_delta: ListDelta::between(&parent_stack.unapplied, &stack.unapplied),
+                hidden_delta: ListDelta::between(&parent_stack.hidden, &stack.hidden),
+            })
+        }
+        _ => SnapshotContent::Full(stack_to_owned(stack)),
+    };
+
+    let blob_oid = repo.blob(serde_json::to_string_pretty(&content)?.as_bytes())?;
+    let refname = snapshot_refname(branch_shorthand, timestamp);
+    repo.reference(&refname, blob_oid, false, "stg snapshot")?;
+    Ok(blob_oid)
+}
+
+/// Clone a [`Stack`] by round-tripping it through its own JSON
+/// representation, since it does not otherwise implement `Clone`.
+fn stack_to_owned(stack: &Stack) -> Stack {
+    serde_json::from_str(&serde_json::to_string(stack).expect("Stack always serializes"))
+        .expect("round-tripping a valid Stack always succeeds")
+}
+
+/// Reconstruct the [`Stack`] at `snapshot_blob` (or the most recent
+/// snapshot, if `None`), replaying any chain of incrementals back to the
+/// nearest full snapshot.
+pub(crate) fn restore(
+    repo: &Repository,
+    branch_shorthand: &str,
+    snapshot_blob: Option<Oid>,
+) -> Result<Stack, Error> {
+    let blob_oid = match snapshot_blob {
+        Some(oid) => oid,
+        None => {
+            let reference = most_recent_snapshot(repo, branch_shorthand)?
+                .ok_or(Error::StGitStackMetadataNotFound)?;
+            reference
+                .target()
+                .ok_or(Error::StGitStackMetadataNotFound)?
+        }
+    };
+
+    let mut chain = Vec::new();
+    let mut current = blob_oid;
+    loop {
+        let blob = repo.find_blob(current)?;
+        let content: SnapshotContent = serde_json::from_slice(blob.content())?;
+        match content {
+            SnapshotContent::Full(stack) => {
+                chain.push(SnapshotContent::Full(stack));
+                break;
+            }
+            SnapshotContent::Incremental(incremental) => {
+                current = incremental.parent;
+                chain.push(SnapshotContent::Incremental(incremental));
+            }
+        }
+    }
+
+    let mut stack = match chain.pop().expect("loop always pushes at least one entry") {
+        SnapshotContent::Full(stack) => stack,
+        SnapshotContent::Incremental(_) => unreachable!("chain always bottoms out at Full"),
+    };
+
+    for entry in chain.into_iter().rev() {
+        let incremental = match entry {
+            SnapshotContent::Incremental(incremental) => incremental,
+            SnapshotContent::Full(_) => unreachable!("only one Full entry, already popped"),
+        };
+
+        stack.head = incremental.new_head;
+        for name in &incremental.removed_patches {
+            stack.patches.remove(name);
+        }
+        for (name, oid) in &incremental.changed_patches {
+            stack.patches.insert(
+                name.clone(),
+                PatchDescriptor {
+                    oid: Oid::from_str(oid)
+                        .map_err(|source| Error::InvalidOid(oid.clone(), source))?,
+                },
+            );
+        }
+        incremental.applied_delta.apply(&mut stack.applied);
+        incremental.unapplied_delta.apply(&mut stack.unapplied);
+        incremental.hidden_delta.apply(&mut stack.hidden);
+    }
+
+    Ok(stack)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn list_delta_round_trips_additions_and_removals() {
+        let before = vec!["a".to_string(), "b".to_string(), "c".to_string()];
+        let after = vec!["b".to_string(), "c".to_string(), "d".to_string()];
+
+        let delta = ListDelta::between(&before, &after);
+        assert_eq!(delta.added, vec!["d".to_string()]);
+        assert_eq!(delta.removed, vec!["a".to_string()]);
+
+        let mut replayed = before;
+        delta.apply(&mut replayed);
+        assert_eq!(
+            replayed,
+            vec!["b".to_string(), "c".to_string(), "d".to_string()]
+        );
+    }
+
+    #[test]
+    fn list_delta_apply_is_idempotent_on_duplicates() {
+        let delta = ListDelta {
+            added: vec!["x".to_string()],
+            removed: vec![],
+        };
+        let mut list = vec!["x".to_string()];
+        delta.apply(&mut list);
+        assert_eq!(list, vec!["x".to_string()]);
+    }
+
+    #[test]
+    fn incremental_snapshot_content_round_trips_through_json() {
+        let incremental = Incremental {
+            parent: Oid::zero(),
+            new_head: Oid::zero(),
+            changed_patches: BTreeMap::from([("p1".to_string(), Oid::zero().to_string())]),
+            removed_patches: vec!["p0".to_string()],
+            applied_delta: ListDelta::between(&[], &["p1".to_string()]),
+            unapplied_delta: ListDelta::between(&["p0".to_string()], &[]),
+            hidden_delta: ListDelta::between(&[], &[]),
+        };
+
+        let content = SnapshotContent::Incremental(incremental);
+        let json = serde_json::to_string(&content).unwrap();
+        let roundtripped: SnapshotContent = serde_json::from_str(&json).unwrap();
+
+        match roundtripped {
+            SnapshotContent::Incremental(incremental) => {
+                assert_eq!(incremental.removed_patches, vec!["p0".to_string()]);
+                assert_eq!(incremental.changed_patches["p1"], Oid::zero().to_string());
+            }
+            SnapshotContent::Full(_) => panic!("expected an Incremental variant"),
+        }
+    }
+}
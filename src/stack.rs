@@ -5,9 +5,10 @@ use std::slice::Iter;
 use std::str;
 
 use chrono::{FixedOffset, NaiveDateTime};
-use git2::{Commit, FileMode, Oid, Reference, Repository, Tree};
+use git2::{Commit, FileMode, Oid, Reference, Repository, Time, Tree};
 
 pub(crate) use crate::error::Error;
+use crate::signing::{commit_possibly_signed, SignMode};
 
 const MAX_PARENTS: usize = 16;
 
@@ -36,10 +37,25 @@ impl Stack {
         }
     }
 
+    /// Load the stack currently recorded at `refs/stacks/<branch>`, ready to
+    /// be mutated and re-committed.
+    ///
+    /// `prev` is set to the ref's current tip commit regardless of whatever
+    /// `prev` was embedded in that commit's own `stack.json` (which records
+    /// *that* state's predecessor, not this freshly-loaded instance's).
+    /// `Stack::commit` relies on `prev` naming the real commit this new
+    /// state is being built on top of, so every caller that goes on to
+    /// call `commit()` must load through here rather than `from_tree`
+    /// directly.
     pub fn from_branch(repo: &Repository, branch_name: Option<&str>) -> Result<Self, Error> {
         let stack_ref = get_stack_ref(repo, branch_name)?;
+        let tip = stack_ref
+            .target()
+            .ok_or(Error::StGitStackMetadataNotFound)?;
         let stack_tree = stack_ref.peel_to_tree()?;
-        Ok(Stack::from_tree(repo, &stack_tree)?)
+        let mut stack = Stack::from_tree(repo, &stack_tree)?;
+        stack.prev = Some(tip);
+        Ok(stack)
     }
 
     fn from_tree(repo: &Repository, tree: &Tree) -> Result<Self, Error> {
@@ -84,7 +100,7 @@ impl Stack {
     ) -> Result<Oid, Error> {
         let prev_state_tree = match self.prev {
             Some(previous) => {
-                let prev_tree = repo.find_tree(previous)?;
+                let prev_tree = repo.find_commit(previous)?.tree()?;
                 let prev_state = Self::from_tree(repo, &prev_tree)?;
                 Some((prev_state, prev_tree))
             }
@@ -99,7 +115,9 @@ impl Stack {
         };
         let simplified_parents: Vec<&Commit> = simplified_parents.iter().collect();
 
-        let simplified_parent = repo.commit(
+        let simplified_parent = commit_possibly_signed(
+            repo,
+            SignMode::Default,
             None,
             &sig,
             &sig,
@@ -156,7 +174,9 @@ impl Stack {
         }
         let parent_commits: Vec<&Commit> = parent_commits.iter().collect();
 
-        let commit_oid = repo.commit(
+        let commit_oid = commit_possibly_signed(
+            repo,
+            SignMode::Default,
             update_ref,
             &sig,
             &sig,
@@ -251,6 +271,135 @@ impl Stack {
 
         Ok(repo.blob(patch_meta.as_bytes())?)
     }
+
+    /// Iterate the stack's operation log: the state `refs/stacks/<branch>`
+    /// currently points at and every state before it in its `prev` chain,
+    /// newest first, reconstructed with [`Stack::from_tree`]. States that
+    /// are not in that chain, such as ones reverted by `stg undo`, are not
+    /// yielded.
+    ///
+    /// This follows `Stack.prev` directly rather than walking the commit
+    /// graph: each state commit's `prev` field already names the real,
+    /// fully-parented commit `Stack::commit` wrote for the previous
+    /// operation (the one whose extra merge-parents keep every applied,
+    /// unapplied, and hidden patch commit reachable). Walking the graph
+    /// instead — e.g. via `repo.revwalk().simplify_first_parent()` — only
+    /// visits the single-parent "simplified" commits `Stack::commit` writes
+    /// alongside each real one, which would yield the wrong OID for every
+    /// entry but the first.
+    pub fn log_iter<'repo>(
+        repo: &'repo Repository,
+        branch_name: Option<&str>,
+    ) -> Result<OperationLogIter<'repo>, Error> {
+        let stack_ref = get_stack_ref(repo, branch_name)?;
+        let tip = stack_ref
+            .target()
+            .ok_or(Error::StGitStackMetadataNotFound)?;
+
+        Ok(OperationLogIter {
+            repo,
+            next: Some(tip),
+        })
+    }
+}
+
+/// One historical entry in a stack's operation log.
+pub(crate) struct LogEntry {
+    pub stack: Stack,
+    pub commit_oid: Oid,
+    pub committer_time: Time,
+    pub message: String,
+}
+
+/// Iterator over a stack's history, yielding one [`LogEntry`] per state
+/// that was committed to `refs/stacks/<branch>`, following `Stack.prev`
+/// back to the initial state.
+pub(crate) struct OperationLogIter<'repo> {
+    repo: &'repo Repository,
+    next: Option<Oid>,
+}
+
+impl<'repo> Iterator for OperationLogIter<'repo> {
+    type Item = Result<LogEntry, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let commit_oid = self.next.take()?;
+
+        let commit = match self.repo.find_commit(commit_oid) {
+            Ok(commit) => commit,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let tree = match commit.tree() {
+            Ok(tree) => tree,
+            Err(e) => return Some(Err(e.into())),
+        };
+        let stack = match Stack::from_tree(self.repo, &tree) {
+            Ok(stack) => stack,
+            Err(e) => return Some(Err(e)),
+        };
+
+        self.next = stack.prev;
+
+        Some(Ok(LogEntry {
+            commit_oid,
+            committer_time: commit.committer().when(),
+            message: commit.message().unwrap_or("").to_string(),
+            stack,
+        }))
+    }
+}
+
+/// The message prefix used for reflog entries written below, so `stg redo`
+/// can tell an undo/redo transition apart from a genuine forward operation
+/// when both happen to touch the same oid.
+pub(crate) const UNDO_REDO_MESSAGE_PREFIX: &str = "undo/redo to: ";
+
+/// Reset `refs/stacks/<branch>` to `entry.commit_oid` and check out the
+/// resulting top of stack, used by both `stg undo` and `stg redo`.
+pub(crate) fn checkout_log_entry(
+    repo: &Repository,
+    branch_name: Option<&str>,
+    entry: &LogEntry,
+) -> Result<(), Error> {
+    let stack_ref = get_stack_ref(repo, branch_name)?;
+    let stack_refname = stack_ref.name().ok_or(Error::StGitNonUtf8Name)?.to_string();
+    repo.reference(
+        &stack_refname,
+        entry.commit_oid,
+        true,
+        &format!("{}{}", UNDO_REDO_MESSAGE_PREFIX, entry.message),
+    )?;
+
+    let top_commit = repo.find_commit(entry.stack.top())?;
+    repo.reset(top_commit.as_object(), git2::ResetType::Mixed, None)?;
+    Ok(())
+}
+
+/// Write `stack` as a new state commit on `refs/stacks/<branch>` and check
+/// out the resulting top of stack, used by `stg snapshot --restore` to pin
+/// the stack back to an arbitrary reconstructed prior state.
+///
+/// `stack.prev`, as reconstructed from a snapshot, names whatever commit was
+/// current when the snapshot was taken; like [`Stack::from_branch`], this
+/// overrides it to the ref's real current tip before committing, so the
+/// operation log stays a consistent chain regardless of how long ago the
+/// restored snapshot was written.
+pub(crate) fn checkout_stack(
+    repo: &Repository,
+    branch_name: Option<&str>,
+    mut stack: Stack,
+    message: &str,
+) -> Result<(), Error> {
+    let stack_refname = stack_refname(repo, branch_name)?;
+    let tip = get_stack_ref(repo, branch_name)?
+        .target()
+        .ok_or(Error::StGitStackMetadataNotFound)?;
+    stack.prev = Some(tip);
+    stack.commit(repo, Some(&stack_refname), message)?;
+
+    let top_commit = repo.find_commit(stack.top())?;
+    repo.reset(top_commit.as_object(), git2::ResetType::Mixed, None)?;
+    Ok(())
 }
 
 pub struct AllPatchesIter<'a>(Chain<Chain<Iter<'a, String>, Iter<'a, String>>, Iter<'a, String>>);
@@ -403,6 +552,30 @@ fn get_branch_ref<'repo>(
     }
 }
 
+/// The fully-qualified `refs/stacks/<branch>` name for `branch_name` (or the
+/// current branch), for commands that need to read its reflog directly
+/// (e.g. `stg redo`).
+pub(crate) fn stack_refname(repo: &Repository, branch_name: Option<&str>) -> Result<String, Error> {
+    Ok(stack_refname_from_branch_shorthand(&branch_shorthand(
+        repo,
+        branch_name,
+    )?))
+}
+
+/// The shorthand name (e.g. `main`) of `branch_name`, or the current branch
+/// if `None`. Commands that key auxiliary refs off the branch name (review
+/// notes, snapshots, ...) use this rather than re-deriving it themselves.
+pub(crate) fn branch_shorthand(
+    repo: &Repository,
+    branch_name: Option<&str>,
+) -> Result<String, Error> {
+    let branch_ref = get_branch_ref(repo, branch_name)?;
+    Ok(branch_ref
+        .shorthand()
+        .ok_or(Error::StGitNonUtf8Name)?
+        .to_string())
+}
+
 fn get_stack_ref<'repo>(
     repo: &'repo Repository,
     branch_name: Option<&str>,
@@ -413,3 +586,109 @@ fn get_stack_ref<'repo>(
     repo.find_reference(&stack_refname)
         .map_err(|_| Error::StGitStackNotInitialized(branch_shorthand.into()))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    /// A throwaway repo with an initial empty commit on `master` checked
+    /// out, with a local `user.name`/`user.email` so `repo.signature()`
+    /// doesn't depend on the machine's global git config.
+    fn init_test_repo() -> (TempDir, Repository) {
+        let dir = TempDir::new().unwrap();
+        let repo = Repository::init(dir.path()).unwrap();
+        {
+            let mut config = repo.config().unwrap();
+            config.set_str("user.name", "Test").unwrap();
+            config.set_str("user.email", "test@example.com").unwrap();
+        }
+        let sig = repo.signature().unwrap();
+        let tree_oid = repo.treebuilder(None).unwrap().write().unwrap();
+        let tree = repo.find_tree(tree_oid).unwrap();
+        let commit_oid = repo
+            .commit(Some("HEAD"), &sig, &sig, "root", &tree, &[])
+            .unwrap();
+        repo.reference("refs/heads/master", commit_oid, false, "init")
+            .unwrap();
+        repo.set_head("refs/heads/master").unwrap();
+        (dir, repo)
+    }
+
+    /// Mimic what `stg new` does at the stack level: commit a new, empty
+    /// patch on top of the current top of stack and record it as applied.
+    fn new_patch(repo: &Repository, patch_name: &str, message: &str) -> Oid {
+        let branch_name: Option<&str> = None;
+        let mut stack = Stack::from_branch(repo, branch_name).unwrap();
+
+        let sig = repo.signature().unwrap();
+        let parent = repo.find_commit(stack.top()).unwrap();
+        let patch_oid = repo
+            .commit(
+                None,
+                &sig,
+                &sig,
+                message,
+                &parent.tree().unwrap(),
+                &[&parent],
+            )
+            .unwrap();
+
+        stack.applied.push(patch_name.to_string());
+        stack
+            .patches
+            .insert(patch_name.to_string(), PatchDescriptor { oid: patch_oid });
+
+        let stack_refname = stack_refname(repo, branch_name).unwrap();
+        stack.commit(repo, Some(&stack_refname), message).unwrap();
+        patch_oid
+    }
+
+    #[test]
+    fn operation_log_survives_a_second_operation() {
+        let (_dir, repo) = init_test_repo();
+        let branch_name: Option<&str> = None;
+
+        initialize(&repo, branch_name).unwrap();
+        let p1 = new_patch(&repo, "p1", "new p1");
+        let p2 = new_patch(&repo, "p2", "new p2");
+
+        let entries: Vec<LogEntry> = Stack::log_iter(&repo, branch_name)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].message, "new p2");
+        assert_eq!(entries[1].message, "new p1");
+        assert_eq!(entries[2].message, "initialize");
+        assert_eq!(entries[0].stack.applied, vec!["p1", "p2"]);
+        assert_eq!(entries[0].stack.top(), p2);
+        assert_eq!(entries[1].stack.applied, vec!["p1"]);
+        assert_eq!(entries[1].stack.top(), p1);
+
+        // stg undo: back up past "new p2" to the "new p1" state.
+        checkout_log_entry(&repo, branch_name, &entries[1]).unwrap();
+        let undone = Stack::from_branch(&repo, branch_name).unwrap();
+        assert_eq!(undone.applied, vec!["p1"]);
+        assert_eq!(undone.top(), p1);
+        assert_eq!(undone.prev, Some(entries[1].commit_oid));
+
+        // stg redo: reapply "new p2".
+        checkout_log_entry(&repo, branch_name, &entries[0]).unwrap();
+        let redone = Stack::from_branch(&repo, branch_name).unwrap();
+        assert_eq!(redone.applied, vec!["p1", "p2"]);
+        assert_eq!(redone.top(), p2);
+        assert_eq!(redone.prev, Some(entries[0].commit_oid));
+
+        // A third operation after the undo/redo round trip must still chain
+        // onto the real tip rather than a stale `prev` from `stack.json`.
+        let p3 = new_patch(&repo, "p3", "new p3");
+        let after_third: Vec<LogEntry> = Stack::log_iter(&repo, branch_name)
+            .unwrap()
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(after_third.len(), 4);
+        assert_eq!(after_third[0].stack.top(), p3);
+        assert_eq!(after_third[0].stack.applied, vec!["p1", "p2", "p3"]);
+    }
+}